@@ -9,10 +9,24 @@
 // except according to those terms.
 
 //! Code for type-checking closure expressions.
+//!
+//! Partial implementation note (`LunixChewBahka/rust#chunk0-2`): this
+//! file deduces *both* the expected yield and return types of a
+//! generator from a pending `Generator` projection obligation (see
+//! `ExpectedGeneratorTypes`), but only the return type is actually fed
+//! into the body's type-checking, via `demand_eqtype` in
+//! `check_closure`. Using the yield type the same way requires
+//! `check_fn`, in `check/mod.rs`, to accept an expected yield type and
+//! use it in place of a fresh inference variable when checking `yield`
+//! expressions; `check/mod.rs` is untouched by this series, so that half
+//! of the request is not delivered. `ExpectedGeneratorTypes::yield_ty` is
+//! computed and threaded as far as this file allows, but has no effect
+//! on checking until that follow-up lands.
 
 use super::{check_fn, Expectation, FnCtxt};
 
 use astconv::AstConv;
+use rustc::hir::def_id::DefId;
 use rustc::infer::type_variable::TypeVariableOrigin;
 use rustc::ty::{self, ToPolyTraitRef, Ty};
 use rustc::ty::subst::Substs;
@@ -21,6 +35,35 @@ use std::iter;
 use syntax::abi::Abi;
 use rustc::hir;
 
+/// Expected yield/return types for a generator, gleaned from a pending
+/// `Generator` trait obligation on the closure's expected type (see
+/// `deduce_generator_sig_from_projection`). Either field may be absent:
+/// a single expected type pins down only one of `Yield`/`Return`, so we
+/// accumulate partial information from multiple projections rather than
+/// requiring the full set up front.
+///
+/// Only `return_ty` is actually consumed today; see the module-level
+/// partial-implementation note above for `yield_ty`.
+///
+/// FIXME: once generators grow explicit resume arguments, add a
+/// `resume_ty` field here alongside `yield_ty` and `return_ty`.
+#[derive(Clone, Default)]
+struct ExpectedGeneratorTypes<'tcx> {
+    yield_ty: Option<Ty<'tcx>>,
+    return_ty: Option<Ty<'tcx>>,
+}
+
+impl<'tcx> ExpectedGeneratorTypes<'tcx> {
+    /// Combines two (possibly partial) sets of expectations, preferring
+    /// `self`'s fields when both specify the same component.
+    fn or(self, other: Self) -> Self {
+        ExpectedGeneratorTypes {
+            yield_ty: self.yield_ty.or(other.yield_ty),
+            return_ty: self.return_ty.or(other.return_ty),
+        }
+    }
+}
+
 impl<'a, 'gcx, 'tcx> FnCtxt<'a, 'gcx, 'tcx> {
     pub fn check_expr_closure(&self,
                               expr: &hir::Expr,
@@ -36,12 +79,12 @@ impl<'a, 'gcx, 'tcx> FnCtxt<'a, 'gcx, 'tcx> {
         // It's always helpful for inference if we know the kind of
         // closure sooner rather than later, so first examine the expected
         // type, and see if can glean a closure kind from there.
-        let (expected_sig, expected_kind) = match expected.to_option(self) {
+        let (expected_sig, expected_kind, expected_gen_types) = match expected.to_option(self) {
             Some(ty) => self.deduce_expectations_from_expected_type(ty),
-            None => (None, None),
+            None => (None, None, ExpectedGeneratorTypes::default()),
         };
         let body = self.tcx.hir.body(body_id);
-        self.check_closure(expr, expected_kind, decl, body, expected_sig)
+        self.check_closure(expr, expected_kind, decl, body, expected_sig, expected_gen_types)
     }
 
     fn check_closure(&self,
@@ -49,7 +92,8 @@ impl<'a, 'gcx, 'tcx> FnCtxt<'a, 'gcx, 'tcx> {
                      opt_kind: Option<ty::ClosureKind>,
                      decl: &'gcx hir::FnDecl,
                      body: &'gcx hir::Body,
-                     expected_sig: Option<ty::FnSig<'tcx>>)
+                     expected_sig: Option<ty::FnSig<'tcx>>,
+                     expected_gen_types: ExpectedGeneratorTypes<'tcx>)
                      -> Ty<'tcx> {
         debug!("check_closure opt_kind={:?} expected_sig={:?}",
                opt_kind,
@@ -81,6 +125,17 @@ impl<'a, 'gcx, 'tcx> FnCtxt<'a, 'gcx, 'tcx> {
                                                             self.param_env,
                                                             &fn_sig);
 
+        if let Some(return_ty) = expected_gen_types.return_ty {
+            // Without an explicit `-> R` on the generator, `fn_sig.output()`
+            // is a fresh inference variable; pin it to what the
+            // `Generator::Return` projection told us.
+            self.demand_eqtype(expr.span, return_ty, fn_sig.output());
+        }
+
+        // `expected_gen_types.yield_ty` has no slot to go into here unlike
+        // `return_ty` above; see the module-level partial-implementation
+        // note for why (`check_fn` needs to change, and isn't touched by
+        // this series).
         let interior = check_fn(self, self.param_env, fn_sig, decl, expr.id, body, true).1;
 
         if let Some(interior) = interior {
@@ -126,7 +181,7 @@ impl<'a, 'gcx, 'tcx> FnCtxt<'a, 'gcx, 'tcx> {
     fn deduce_expectations_from_expected_type
         (&self,
          expected_ty: Ty<'tcx>)
-         -> (Option<ty::FnSig<'tcx>>, Option<ty::ClosureKind>) {
+         -> (Option<ty::FnSig<'tcx>>, Option<ty::ClosureKind>, ExpectedGeneratorTypes<'tcx>) {
         debug!("deduce_expectations_from_expected_type(expected_ty={:?})",
                expected_ty);
 
@@ -140,18 +195,99 @@ impl<'a, 'gcx, 'tcx> FnCtxt<'a, 'gcx, 'tcx> {
                     .next();
                 let kind = object_type.principal()
                     .and_then(|p| self.tcx.lang_items().fn_trait_kind(p.def_id()));
-                (sig, kind)
+                let gen_types = object_type.projection_bounds()
+                    .filter_map(|pb| {
+                        let pb = pb.with_self_ty(self.tcx, self.tcx.types.err);
+                        self.deduce_generator_sig_from_projection(&pb)
+                    })
+                    .fold(ExpectedGeneratorTypes::default(), |acc, cur| acc.or(cur));
+                (sig, kind, gen_types)
             }
             ty::TyInfer(ty::TyVar(vid)) => self.deduce_expectations_from_obligations(vid),
-            ty::TyFnPtr(sig) => (Some(sig.skip_binder().clone()), Some(ty::ClosureKind::Fn)),
-            _ => (None, None),
+            ty::TyFnPtr(sig) => {
+                (Some(sig.skip_binder().clone()),
+                 Some(ty::ClosureKind::Fn),
+                 ExpectedGeneratorTypes::default())
+            }
+
+            // `&dyn Fn(..)` (and `&&dyn Fn(..)`, etc.): a reference is
+            // structurally transparent to the `Fn` bound it refers to,
+            // so recurse into the referent.
+            ty::TyRef(_, mt) => self.deduce_expectations_from_expected_type(mt.ty),
+
+            // `Box<dyn Fn(..)>`, `Rc<dyn Fn(..)>`, `Arc<dyn Fn(..)>`:
+            // likewise transparent, since none of these carry bounds of
+            // their own beyond those on their pointee.
+            ty::TyAdt(def, substs) if def.is_box() || self.is_transparent_smart_pointer(def) => {
+                self.deduce_expectations_from_expected_type(substs.type_at(0))
+            }
+
+            // `impl Fn(..)`: the bounds live on the anonymized type's
+            // predicates rather than on a `TyDynamic`, so read them off
+            // `predicates_of` instead of `projection_bounds`/`principal`.
+            ty::TyAnon(def_id, substs) => self.deduce_expectations_from_anon(def_id, substs),
+
+            _ => (None, None, ExpectedGeneratorTypes::default()),
+        }
+    }
+
+    /// `Rc<T>`/`Arc<T>` have no lang item the way `Box<T>` does (via
+    /// `owned_box`), so there's no principled way to recognize them in
+    /// general. Match them by their well-known absolute path instead.
+    /// This is deliberately narrow: we peel only these two specific,
+    /// well-known reference-counted wrappers, not arbitrary
+    /// single-type-parameter newtypes, which could easily carry bounds
+    /// that don't apply to their inner type.
+    fn is_transparent_smart_pointer(&self, def: &'tcx ty::AdtDef) -> bool {
+        match &self.tcx.item_path_str(def.did)[..] {
+            "std::rc::Rc" | "std::sync::Arc" => true,
+            _ => false,
         }
     }
 
+    /// Like the `TyDynamic` arm of `deduce_expectations_from_expected_type`,
+    /// but for an `impl Trait` (`TyAnon`) whose bounds are found by
+    /// instantiating `predicates_of` rather than by walking object bounds.
+    fn deduce_expectations_from_anon
+        (&self,
+         def_id: DefId,
+         substs: &'tcx Substs<'tcx>)
+         -> (Option<ty::FnSig<'tcx>>, Option<ty::ClosureKind>, ExpectedGeneratorTypes<'tcx>) {
+        let predicates = self.tcx.predicates_of(def_id).instantiate(self.tcx, substs).predicates;
+
+        let sig = predicates.iter()
+            .filter_map(|predicate| match *predicate {
+                ty::Predicate::Projection(ref proj) => self.deduce_sig_from_projection(proj),
+                _ => None,
+            })
+            .next();
+
+        let kind = predicates.iter()
+            .filter_map(|predicate| match *predicate {
+                ty::Predicate::Trait(ref data) => {
+                    self.tcx.lang_items().fn_trait_kind(data.to_poly_trait_ref().def_id())
+                }
+                _ => None,
+            })
+            .fold(None,
+                  |best, cur| Some(best.map_or(cur, |best| cmp::min(best, cur))));
+
+        let gen_types = predicates.iter()
+            .filter_map(|predicate| match *predicate {
+                ty::Predicate::Projection(ref proj) => {
+                    self.deduce_generator_sig_from_projection(proj)
+                }
+                _ => None,
+            })
+            .fold(ExpectedGeneratorTypes::default(), |acc, cur| acc.or(cur));
+
+        (sig, kind, gen_types)
+    }
+
     fn deduce_expectations_from_obligations
         (&self,
          expected_vid: ty::TyVid)
-         -> (Option<ty::FnSig<'tcx>>, Option<ty::ClosureKind>) {
+         -> (Option<ty::FnSig<'tcx>>, Option<ty::ClosureKind>, ExpectedGeneratorTypes<'tcx>) {
         let fulfillment_cx = self.fulfillment_cx.borrow();
         // Here `expected_ty` is known to be a type inference variable.
 
@@ -210,7 +346,25 @@ impl<'a, 'gcx, 'tcx> FnCtxt<'a, 'gcx, 'tcx> {
             .fold(None,
                   |best, cur| Some(best.map_or(cur, |best| cmp::min(best, cur))));
 
-        (expected_sig, expected_kind)
+        // As with `expected_kind` above, a `Generator` projection obligation
+        // may independently pin down the yield and/or return type; collect
+        // whatever either projection contributes.
+        let expected_gen_types = fulfillment_cx.pending_obligations()
+            .iter()
+            .map(|obligation| &obligation.obligation)
+            .filter_map(|obligation| {
+                match obligation.predicate {
+                    ty::Predicate::Projection(ref proj_predicate) => {
+                        let trait_ref = proj_predicate.to_poly_trait_ref(self.tcx);
+                        self.self_type_matches_expected_vid(trait_ref, expected_vid)
+                            .and_then(|_| self.deduce_generator_sig_from_projection(proj_predicate))
+                    }
+                    _ => None,
+                }
+            })
+            .fold(ExpectedGeneratorTypes::default(), |acc, cur| acc.or(cur));
+
+        (expected_sig, expected_kind, expected_gen_types)
     }
 
     /// Given a projection like "<F as Fn(X)>::Result == Y", we can deduce
@@ -256,6 +410,40 @@ impl<'a, 'gcx, 'tcx> FnCtxt<'a, 'gcx, 'tcx> {
         Some(fn_sig)
     }
 
+    /// Given a projection like `<G as Generator>::Yield == Y` (or
+    /// `<G as Generator>::Return == R`), record the yield/return type it
+    /// pins down. This mirrors `deduce_sig_from_projection`, but for the
+    /// single `Generator` trait rather than the `Fn`/`FnMut`/`FnOnce`
+    /// family: there's no "kind" to recover here, just one of the two
+    /// associated types.
+    fn deduce_generator_sig_from_projection(&self,
+                                            projection: &ty::PolyProjectionPredicate<'tcx>)
+                                            -> Option<ExpectedGeneratorTypes<'tcx>> {
+        let tcx = self.tcx;
+
+        debug!("deduce_generator_sig_from_projection({:?})", projection);
+
+        let trait_ref = projection.to_poly_trait_ref(tcx);
+
+        let gen_trait_def_id = match tcx.lang_items().gen_trait() {
+            Some(def_id) => def_id,
+            None => return None,
+        };
+        if trait_ref.def_id() != gen_trait_def_id {
+            return None;
+        }
+
+        let assoc_item = tcx.associated_item(projection.0.projection_ty.item_def_id);
+        let ty = self.resolve_type_vars_if_possible(&projection.0.ty);
+        debug!("deduce_generator_sig_from_projection: {} = {:?}", assoc_item.name, ty);
+
+        match &*assoc_item.name.as_str() {
+            "Yield" => Some(ExpectedGeneratorTypes { yield_ty: Some(ty), return_ty: None }),
+            "Return" => Some(ExpectedGeneratorTypes { yield_ty: None, return_ty: Some(ty) }),
+            _ => None,
+        }
+    }
+
     fn self_type_matches_expected_vid(&self,
                                       trait_ref: ty::PolyTraitRef<'tcx>,
                                       expected_vid: ty::TyVid)
@@ -0,0 +1,30 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// `deduce_expectations_from_expected_type` only peels references, `Box`
+// and the two well-known reference-counted wrappers (`Rc`/`Arc`) to reach
+// a `dyn Fn`/`impl Fn` bound; it must not invent a signature by peeling
+// an arbitrary custom smart pointer. `MyBox` below behaves just like
+// `Box` via `CoerceUnsized`, but isn't one of the known wrappers, so the
+// closure argument still needs an explicit type annotation.
+
+#![feature(coerce_unsized, unsize)]
+
+use std::marker::Unsize;
+use std::ops::CoerceUnsized;
+
+struct MyBox<T: ?Sized>(Box<T>);
+
+impl<T: ?Sized, U: ?Sized> CoerceUnsized<MyBox<U>> for MyBox<T> where T: Unsize<U> {}
+
+fn main() {
+    let _: MyBox<dyn Fn(i32) -> i32> = MyBox(Box::new(|x| x + 1));
+    //~^ ERROR type annotations needed
+}
@@ -0,0 +1,61 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Closures assigned to a `&dyn Fn`, `Box<dyn Fn>`, `Rc<dyn Fn>` or
+// `impl Fn` expected type should have their argument/return types
+// deduced from that expected type, the same way a bare `dyn Fn`
+// expected type already does, so none of these closures need an
+// explicit `|x: i32|` annotation.
+
+#![feature(conservative_impl_trait)]
+
+use std::rc::Rc;
+use std::sync::Arc;
+
+fn via_ref(f: &dyn Fn(i32) -> i32) -> i32 {
+    f(1)
+}
+
+fn via_box(f: Box<dyn Fn(i32) -> i32>) -> i32 {
+    f(1)
+}
+
+fn via_rc(f: Rc<dyn Fn(i32) -> i32>) -> i32 {
+    f(1)
+}
+
+fn via_arc(f: Arc<dyn Fn(i32) -> i32>) -> i32 {
+    f(1)
+}
+
+fn via_double_ref(f: &&dyn Fn(i32) -> i32) -> i32 {
+    f(1)
+}
+
+fn make_impl_fn() -> impl Fn(i32) -> i32 {
+    |x| x + 1
+}
+
+fn main() {
+    assert_eq!(via_ref(&|x| x + 1), 2);
+    assert_eq!(via_box(Box::new(|x| x + 1)), 2);
+    assert_eq!(via_rc(Rc::new(|x| x + 1)), 2);
+    assert_eq!(via_arc(Arc::new(|x| x + 1)), 2);
+
+    // Unsized coercion only applies at the outermost reference of a
+    // coercion site, not recursively through a second layer of
+    // indirection, so `&(&|x| x + 1)` can't coerce straight to
+    // `&&dyn Fn(i32) -> i32` in one expression. Bind the inner
+    // reference with its coerced type first.
+    let r: &dyn Fn(i32) -> i32 = &(|x| x + 1);
+    assert_eq!(via_double_ref(&r), 2);
+
+    assert_eq!(make_impl_fn()(1), 2);
+}
@@ -0,0 +1,43 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// The expected `Generator::Return` type, read off a pending `Generator`
+// projection obligation on an `impl Generator<Yield = .., Return = ..>`
+// return type, is fed into the generator body as an expectation. Without
+// that, the bare integer literal tail expression below would default to
+// `i32` and fail to unify with the `u8` pinned down by `Return = u8`;
+// with it, the literal is checked directly against `u8`.
+//
+// Note: only the return type is deduced this way today, not the yield
+// type, so the `yield` expression below is given an explicit suffix
+// rather than relying on `Yield = u32` to infer it.
+
+#![feature(generators, generator_trait)]
+
+use std::ops::{Generator, GeneratorState};
+
+fn make_gen() -> impl Generator<Yield = u32, Return = u8> {
+    || {
+        yield 1u32;
+        2
+    }
+}
+
+fn main() {
+    let mut gen = make_gen();
+    match gen.resume() {
+        GeneratorState::Yielded(1) => {}
+        _ => panic!("unexpected state"),
+    }
+    match gen.resume() {
+        GeneratorState::Complete(2) => {}
+        _ => panic!("unexpected state"),
+    }
+}